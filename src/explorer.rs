@@ -1,5 +1,6 @@
 use std::{
-    collections::{HashSet, hash_set},
+    collections::{HashMap, HashSet, hash_set},
+    fs,
     path::{Path, PathBuf},
 };
 
@@ -10,6 +11,48 @@ use cgroups_rs::{
 use derive_builder::Builder;
 use walkdir::WalkDir;
 
+/// A non-glob include criterion, dispatched from a pattern-kind prefix on an `include`
+/// string: `re:`, `path:`, or `rootfilesin:`. Plain globs (the `glob:` prefix, or no prefix)
+/// go straight into `IncludeMatcher::glob_set` instead, so the common case still gets
+/// O(1)-per-path matching.
+#[derive(Clone)]
+enum IncludeEntry {
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+    /// Matches only the exact relative path given, literally.
+    Path(PathBuf),
+    /// Matches only the immediate children of the given relative directory.
+    RootFilesIn(PathBuf),
+}
+
+impl IncludeEntry {
+    fn matches(&self, path: &Path) -> bool {
+        match self {
+            #[cfg(feature = "regex")]
+            IncludeEntry::Regex(pattern) => pattern.is_match(&path.to_string_lossy()),
+            IncludeEntry::Path(exact) => path == exact,
+            IncludeEntry::RootFilesIn(dir) => path.parent() == Some(dir.as_path()),
+        }
+    }
+}
+
+/// A compiled include matcher: a `GlobSet` for the common `glob:`/bare-pattern case (O(1)
+/// per path), plus a small side-list of the non-glob criteria (`re:`, `path:`,
+/// `rootfilesin:`), and the precomputed literal prefix of every criterion, used to prune
+/// directory descent.
+#[derive(Clone)]
+struct IncludeMatcher {
+    glob_set: globset::GlobSet,
+    extra: Vec<IncludeEntry>,
+    prefixes: Vec<(Vec<String>, bool)>,
+}
+
+impl IncludeMatcher {
+    fn is_empty(&self) -> bool {
+        self.glob_set.is_empty() && self.extra.is_empty()
+    }
+}
+
 /// An interface to explore cgroups in the system.
 ///
 /// # Example
@@ -30,24 +73,46 @@ pub struct Explorer {
     /// The cgroup hierarchy to explore.
     hierarchy: Box<dyn Hierarchy>,
 
-    /// The globs to include in the exploration.
+    /// The criteria to include in the exploration. Each string may be prefixed with a pattern
+    /// kind: `glob:` (the default), `re:`, `path:` (exact literal relative path), or
+    /// `rootfilesin:` (direct children of a directory only).
     #[builder(field(ty = "Vec<String>", build = "parse_include(self.include)?"))]
-    include: Vec<glob::Pattern>,
-    /// The regexes to match group names against.
+    include: IncludeMatcher,
+    /// The regexes to match group names against, compiled into a single matcher.
     #[cfg_attr(
         feature = "regex",
         builder(field(ty = "Vec<String>", build = "parse_include_regex(self.include_regex)?"))
     )]
     #[cfg(feature = "regex")]
-    include_regex: Vec<regex::Regex>,
+    include_regex: regex::RegexSet,
+
+    /// The globs to exclude from the exploration, applied after `include`. Compiled with the
+    /// same `GlobSet` dialect as `include`, so a pattern means the same thing in either list.
+    #[builder(field(ty = "Vec<String>", build = "parse_exclude(self.exclude)?"))]
+    exclude: globset::GlobSet,
+    /// The regexes whose matches are excluded from the exploration, applied after `include_regex`.
+    #[cfg_attr(
+        feature = "regex",
+        builder(field(ty = "Vec<String>", build = "parse_exclude_regex(self.exclude_regex)?"))
+    )]
+    #[cfg(feature = "regex")]
+    exclude_regex: Vec<regex::Regex>,
+
+    /// The controllers that must be enabled on a cgroup for it to be yielded.
+    #[builder(default, setter(name = "with_controllers"))]
+    controllers: Vec<String>,
 }
 
 /// An iterator over cgroups in the system that match the globs.
 struct CgroupsV2Iterator {
-    walker: walkdir::IntoIter,
-    include: Vec<glob::Pattern>,
+    walker: walkdir::FilterEntry<walkdir::IntoIter, Box<dyn FnMut(&walkdir::DirEntry) -> bool>>,
+    include: IncludeMatcher,
     #[cfg(feature = "regex")]
-    include_regex: Vec<regex::Regex>,
+    include_regex: regex::RegexSet,
+    exclude: globset::GlobSet,
+    #[cfg(feature = "regex")]
+    exclude_regex: Vec<regex::Regex>,
+    controllers: Vec<String>,
     base_path: PathBuf,
 }
 
@@ -90,15 +155,30 @@ impl Explorer {
 
     fn iter_cgroups_v2(&self) -> CgroupsV2Iterator {
         let base_path = self.hierarchy.root();
+        let prefixes = self.include.prefixes.clone();
+        let filter_base = base_path.clone();
         let walker = WalkDir::new(base_path.clone())
             .min_depth(1)
             .sort_by_file_name()
-            .into_iter();
+            .into_iter()
+            .filter_entry(Box::new(move |entry| {
+                if !entry.file_type().is_dir() {
+                    return true;
+                }
+                let Ok(relative_path) = entry.path().strip_prefix(&filter_base) else {
+                    return true;
+                };
+                should_descend(relative_path, &prefixes)
+            }) as Box<dyn FnMut(&walkdir::DirEntry) -> bool>);
         CgroupsV2Iterator {
             walker,
             include: self.include.clone(),
             #[cfg(feature = "regex")]
             include_regex: self.include_regex.clone(),
+            exclude: self.exclude.clone(),
+            #[cfg(feature = "regex")]
+            exclude_regex: self.exclude_regex.clone(),
+            controllers: self.controllers.clone(),
             base_path,
         }
     }
@@ -109,13 +189,14 @@ impl Explorer {
         let base_path = hierarchy.root();
 
         let mut matching_rel_paths = HashSet::new();
+        let mut controllers_by_path: HashMap<PathBuf, HashSet<String>> = HashMap::new();
         for subsystem in subystems {
             let name = subsystem.controller_name();
             let walker = WalkDir::new(base_path.join(&name))
                 .min_depth(1)
                 .sort_by_file_name()
                 .into_iter();
-            let base_controller_path = base_path.join(name);
+            let base_controller_path = base_path.join(&name);
             for entry in walker {
                 let Ok(entry) = entry else { continue };
                 let path = entry.path();
@@ -128,18 +209,37 @@ impl Explorer {
                 if relative_path.components().count() == 0 {
                     continue;
                 }
+                controllers_by_path
+                    .entry(relative_path.to_path_buf())
+                    .or_default()
+                    .insert(name.clone());
+
                 #[cfg(feature = "regex")]
                 let should_include = path_matches_include(&self.include, relative_path)
                     || path_matches_include_regex(&self.include_regex, relative_path);
                 #[cfg(not(feature = "regex"))]
                 let should_include = path_matches_include(&self.include, relative_path);
 
-                if should_include {
+                #[cfg(feature = "regex")]
+                let should_exclude = path_matches_exclude(&self.exclude, relative_path)
+                    || path_matches_exclude_regex(&self.exclude_regex, relative_path);
+                #[cfg(not(feature = "regex"))]
+                let should_exclude = path_matches_exclude(&self.exclude, relative_path);
+
+                if should_include && !should_exclude {
                     matching_rel_paths.insert(relative_path.to_path_buf());
                 }
             }
         }
 
+        if !self.controllers.is_empty() {
+            matching_rel_paths.retain(|path| {
+                controllers_by_path
+                    .get(path)
+                    .is_some_and(|present| self.controllers.iter().all(|c| present.contains(c)))
+            });
+        }
+
         CgroupsV1Iterator {
             discovered: matching_rel_paths.into_iter(),
         }
@@ -171,6 +271,19 @@ impl Iterator for CgroupsV2Iterator {
                     if !path_matches_include_regex(&self.include_regex, relative_path) {
                         continue;
                     }
+                    if path_matches_exclude(&self.exclude, relative_path) {
+                        continue;
+                    }
+                    #[cfg(feature = "regex")]
+                    if path_matches_exclude_regex(&self.exclude_regex, relative_path) {
+                        continue;
+                    }
+                    if !self.controllers.is_empty() {
+                        let enabled = read_enabled_controllers(&self.base_path.join(relative_path));
+                        if !self.controllers.iter().all(|c| enabled.contains(c)) {
+                            continue;
+                        }
+                    }
                     return Some(Cgroup::load(Box::new(V2::new()), relative_path));
                 }
                 Some(Err(_e)) => return None,
@@ -190,48 +303,302 @@ impl Iterator for CgroupsV1Iterator {
     }
 }
 
-fn path_matches_include(include: &[glob::Pattern], path: &Path) -> bool {
+/// Computes, for one include glob, the leading path components that contain no wildcard
+/// metacharacters (`*`, `?`, `[`, `{`), along with whether the pattern contains a `**` component
+/// that makes it unsafe to prune subtree descent based on that pattern alone.
+fn required_literal_prefix(pattern: &str) -> (Vec<String>, bool) {
+    let mut components = Vec::new();
+    for component in pattern.split('/') {
+        if component.contains("**") {
+            return (components, true);
+        }
+        if component.contains(['*', '?', '[', '{']) {
+            break;
+        }
+        components.push(component.to_string());
+    }
+    (components, false)
+}
+
+/// Decides whether a directory at `relative_path` should be descended into, given the
+/// precomputed literal prefixes of the include globs. A directory is kept if it is
+/// component-wise compatible with at least one pattern's fixed prefix.
+fn should_descend(relative_path: &Path, prefixes: &[(Vec<String>, bool)]) -> bool {
+    if prefixes.is_empty() {
+        return true;
+    }
+    let components: Vec<String> = relative_path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    prefixes.iter().any(|(prefix, unbounded)| {
+        if *unbounded {
+            return true;
+        }
+        let len = components.len().min(prefix.len());
+        components[..len] == prefix[..len]
+    })
+}
+
+/// Reads and parses the `cgroup.controllers` file for a v2 cgroup directory, returning the set
+/// of enabled controller names. Called once per directory so checking several requested
+/// controllers doesn't re-open the file.
+fn read_enabled_controllers(dir: &Path) -> HashSet<String> {
+    fs::read_to_string(dir.join("cgroup.controllers"))
+        .map(|contents| contents.split_whitespace().map(String::from).collect())
+        .unwrap_or_default()
+}
+
+fn path_matches_include(include: &IncludeMatcher, path: &Path) -> bool {
     if include.is_empty() {
         return true;
     }
-    let path_str = path.to_string_lossy();
-    include.iter().any(|pattern| pattern.matches(&path_str))
+    include.glob_set.is_match(path) || include.extra.iter().any(|entry| entry.matches(path))
 }
 
 #[cfg(feature = "regex")]
-fn path_matches_include_regex(include: &[regex::Regex], path: &Path) -> bool {
+fn path_matches_include_regex(include: &regex::RegexSet, path: &Path) -> bool {
     if include.is_empty() {
         return true;
     }
+    include.is_match(&path.to_string_lossy())
+}
+
+fn path_matches_exclude(exclude: &globset::GlobSet, path: &Path) -> bool {
+    !exclude.is_empty() && exclude.is_match(path)
+}
+
+#[cfg(feature = "regex")]
+fn path_matches_exclude_regex(exclude: &[regex::Regex], path: &Path) -> bool {
+    if exclude.is_empty() {
+        return false;
+    }
     let path_str = path.to_string_lossy();
-    include.iter().any(|pattern| pattern.is_match(&path_str))
+    exclude.iter().any(|pattern| pattern.is_match(&path_str))
 }
 
-fn parse_include(include: Vec<String>) -> Result<Vec<glob::Pattern>, ExplorerBuilderError> {
-    if include.is_empty() {
-        Ok(Vec::new())
-    } else {
-        include
-            .into_iter()
-            .map(|include| {
-                glob::Pattern::new(&include)
-                    .map_err(|e| ExplorerBuilderError::ValidationError(e.to_string()))
-            })
+/// The result of dispatching a single `include` string on its pattern-kind prefix: either a
+/// glob (the common case, destined for the `GlobSet`) or one of the non-glob special cases.
+enum ParsedInclude {
+    Glob(String),
+    Special(IncludeEntry),
+}
+
+/// Parses a single `include` string, dispatching on its pattern-kind prefix: `path:` for an
+/// exact literal relative path, `rootfilesin:` for direct children of a directory, `re:` for
+/// a regex, `glob:` (or no prefix) for a glob. `re:` requires the `regex` feature; without it,
+/// this returns an error rather than silently reinterpreting the pattern as a glob.
+fn parse_include_entry(pattern: &str) -> Result<ParsedInclude, ExplorerBuilderError> {
+    if let Some(rest) = pattern.strip_prefix("path:") {
+        return Ok(ParsedInclude::Special(IncludeEntry::Path(PathBuf::from(
+            rest,
+        ))));
+    }
+    if let Some(rest) = pattern.strip_prefix("rootfilesin:") {
+        return Ok(ParsedInclude::Special(IncludeEntry::RootFilesIn(
+            PathBuf::from(rest),
+        )));
+    }
+    if let Some(rest) = pattern.strip_prefix("re:") {
+        #[cfg(feature = "regex")]
+        {
+            let regex = regex::Regex::new(rest)
+                .map_err(|e| ExplorerBuilderError::ValidationError(e.to_string()))?;
+            return Ok(ParsedInclude::Special(IncludeEntry::Regex(regex)));
+        }
+        #[cfg(not(feature = "regex"))]
+        {
+            return Err(ExplorerBuilderError::ValidationError(format!(
+                "include pattern {pattern:?} uses the `re:` prefix, which requires the `regex` feature"
+            )));
+        }
+    }
+    let glob_pattern = pattern.strip_prefix("glob:").unwrap_or(pattern);
+    Ok(ParsedInclude::Glob(glob_pattern.to_string()))
+}
+
+/// Computes the literal prefix used to prune directory descent for a single include entry.
+/// Regex entries can't be pruned on, so they're always treated as unbounded.
+fn include_entry_prefix(entry: &IncludeEntry) -> (Vec<String>, bool) {
+    let path_components = |path: &Path| -> Vec<String> {
+        path.components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
             .collect()
+    };
+    match entry {
+        #[cfg(feature = "regex")]
+        IncludeEntry::Regex(_) => (Vec::new(), true),
+        IncludeEntry::Path(path) => (path_components(path), false),
+        IncludeEntry::RootFilesIn(dir) => (path_components(dir), false),
     }
 }
 
+fn parse_include(include: Vec<String>) -> Result<IncludeMatcher, ExplorerBuilderError> {
+    let mut builder = globset::GlobSetBuilder::new();
+    let mut extra = Vec::new();
+    let mut prefixes = Vec::with_capacity(include.len());
+    for pattern in &include {
+        match parse_include_entry(pattern)? {
+            ParsedInclude::Glob(glob_pattern) => {
+                prefixes.push(required_literal_prefix(&glob_pattern));
+                let glob = globset::Glob::new(&glob_pattern)
+                    .map_err(|e| ExplorerBuilderError::ValidationError(e.to_string()))?;
+                builder.add(glob);
+            }
+            ParsedInclude::Special(entry) => {
+                prefixes.push(include_entry_prefix(&entry));
+                extra.push(entry);
+            }
+        }
+    }
+    let glob_set = builder
+        .build()
+        .map_err(|e| ExplorerBuilderError::ValidationError(e.to_string()))?;
+    Ok(IncludeMatcher {
+        glob_set,
+        extra,
+        prefixes,
+    })
+}
+
 #[cfg(feature = "regex")]
-fn parse_include_regex(include: Vec<String>) -> Result<Vec<regex::Regex>, ExplorerBuilderError> {
-    if include.is_empty() {
+fn parse_include_regex(include: Vec<String>) -> Result<regex::RegexSet, ExplorerBuilderError> {
+    regex::RegexSet::new(&include).map_err(|e| ExplorerBuilderError::ValidationError(e.to_string()))
+}
+
+/// Compiles the `exclude` globs into a `GlobSet`, the same compiled matcher type `include`
+/// uses (see `parse_include`), so the two lists share one glob dialect.
+fn parse_exclude(exclude: Vec<String>) -> Result<globset::GlobSet, ExplorerBuilderError> {
+    let mut builder = globset::GlobSetBuilder::new();
+    for pattern in &exclude {
+        let glob = globset::Glob::new(pattern)
+            .map_err(|e| ExplorerBuilderError::ValidationError(e.to_string()))?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map_err(|e| ExplorerBuilderError::ValidationError(e.to_string()))
+}
+
+#[cfg(feature = "regex")]
+fn parse_exclude_regex(exclude: Vec<String>) -> Result<Vec<regex::Regex>, ExplorerBuilderError> {
+    if exclude.is_empty() {
         Ok(Vec::new())
     } else {
-        include
+        exclude
             .into_iter()
-            .map(|include| {
-                regex::Regex::new(&include)
+            .map(|exclude| {
+                regex::Regex::new(&exclude)
                     .map_err(|e| ExplorerBuilderError::ValidationError(e.to_string()))
             })
             .collect()
     }
 }
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn required_literal_prefix_stops_at_first_wildcard() {
+        assert_eq!(
+            required_literal_prefix("user.slice/foo.scope"),
+            (vec!["user.slice".to_string(), "foo.scope".to_string()], false)
+        );
+        assert_eq!(
+            required_literal_prefix("user.slice/*"),
+            (vec!["user.slice".to_string()], false)
+        );
+        assert_eq!(required_literal_prefix("*.scope"), (Vec::new(), false));
+    }
+
+    #[test]
+    fn required_literal_prefix_disables_pruning_on_double_star() {
+        assert_eq!(
+            required_literal_prefix("user.slice/**/foo.scope"),
+            (vec!["user.slice".to_string()], true)
+        );
+    }
+
+    #[test]
+    fn should_descend_prunes_incompatible_directories() {
+        let prefixes = vec![(vec!["user.slice".to_string()], false)];
+        assert!(should_descend(Path::new("user.slice"), &prefixes));
+        assert!(should_descend(Path::new("user.slice/foo.scope"), &prefixes));
+        assert!(!should_descend(Path::new("system.slice"), &prefixes));
+    }
+
+    #[test]
+    fn should_descend_with_no_include_patterns_always_descends() {
+        assert!(should_descend(Path::new("anything"), &[]));
+    }
+
+    #[test]
+    fn should_descend_is_unbounded_for_double_star_patterns() {
+        let prefixes = vec![(vec!["user.slice".to_string()], true)];
+        assert!(should_descend(Path::new("system.slice"), &prefixes));
+    }
+
+    #[test]
+    fn parse_include_entry_dispatches_glob_by_default_and_with_prefix() {
+        assert!(matches!(
+            parse_include_entry("*.scope").unwrap(),
+            ParsedInclude::Glob(pattern) if pattern == "*.scope"
+        ));
+        assert!(matches!(
+            parse_include_entry("glob:*.scope").unwrap(),
+            ParsedInclude::Glob(pattern) if pattern == "*.scope"
+        ));
+    }
+
+    #[test]
+    fn parse_include_entry_dispatches_path() {
+        match parse_include_entry("path:user.slice/foo.scope").unwrap() {
+            ParsedInclude::Special(IncludeEntry::Path(path)) => {
+                assert_eq!(path, PathBuf::from("user.slice/foo.scope"));
+            }
+            _ => panic!("expected a Path entry"),
+        }
+    }
+
+    #[test]
+    fn parse_include_entry_dispatches_rootfilesin() {
+        match parse_include_entry("rootfilesin:system.slice").unwrap() {
+            ParsedInclude::Special(IncludeEntry::RootFilesIn(dir)) => {
+                assert_eq!(dir, PathBuf::from("system.slice"));
+            }
+            _ => panic!("expected a RootFilesIn entry"),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "regex")]
+    fn parse_include_entry_dispatches_regex() {
+        match parse_include_entry("re:^foo.*").unwrap() {
+            ParsedInclude::Special(IncludeEntry::Regex(pattern)) => {
+                assert!(pattern.is_match("foobar"));
+            }
+            _ => panic!("expected a Regex entry"),
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "regex"))]
+    fn parse_include_entry_rejects_regex_prefix_without_feature() {
+        assert!(parse_include_entry("re:^foo.*").is_err());
+    }
+
+    #[test]
+    fn include_minus_exclude_matches_the_difference() {
+        let include = parse_include(vec!["user.slice/*".to_string()]).unwrap();
+        let exclude = parse_exclude(vec!["user.slice/session-1.scope".to_string()]).unwrap();
+
+        let kept = Path::new("user.slice/session-2.scope");
+        let dropped = Path::new("user.slice/session-1.scope");
+        let unrelated = Path::new("system.slice/foo.scope");
+
+        assert!(path_matches_include(&include, kept) && !path_matches_exclude(&exclude, kept));
+        assert!(path_matches_include(&include, dropped) && path_matches_exclude(&exclude, dropped));
+        assert!(!path_matches_include(&include, unrelated));
+    }
+}
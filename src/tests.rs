@@ -46,6 +46,140 @@ fn explore_created_cgroups() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+#[serial]
+fn explore_with_controllers_filter() -> anyhow::Result<()> {
+    let h = cgroups_rs::hierarchies::auto();
+
+    let cgroup_name = "test_cgroup_explorer_controllers";
+    let existing_cgroup = Cgroup::load(auto(), auto().root().join(cgroup_name));
+    let _ = existing_cgroup.delete();
+    let cgroup: Cgroup = CgroupBuilder::new(cgroup_name)
+        .memory()
+        .memory_swap_limit(3 * 1024)
+        .memory_soft_limit(512 * 1024)
+        .memory_hard_limit(1024 * 1024)
+        .done()
+        .build(h)
+        .unwrap();
+
+    let with_memory = Explorer::detect_version()
+        .with_controllers(vec!["memory".to_string()])
+        .build()?;
+    let found = with_memory
+        .iter_cgroups()
+        .find(|c| c.path().ends_with(cgroup_name))
+        .expect("cgroup not found with memory controller filter");
+    assert!(found.exists());
+
+    let with_bogus_controller = Explorer::detect_version()
+        .with_controllers(vec!["nonexistent".to_string()])
+        .build()?;
+    assert!(
+        !with_bogus_controller
+            .iter_cgroups()
+            .any(|c| c.path().ends_with(cgroup_name))
+    );
+
+    cgroup.delete()?;
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn explore_glob_include_and_exclude() -> anyhow::Result<()> {
+    let kept_name = "test_cgroup_explorer_kept";
+    let dropped_name = "test_cgroup_explorer_dropped";
+    for name in [kept_name, dropped_name] {
+        let existing_cgroup = Cgroup::load(auto(), auto().root().join(name));
+        let _ = existing_cgroup.delete();
+    }
+    let kept: Cgroup = CgroupBuilder::new(kept_name)
+        .memory()
+        .done()
+        .build(auto())
+        .unwrap();
+    let dropped: Cgroup = CgroupBuilder::new(dropped_name)
+        .memory()
+        .done()
+        .build(auto())
+        .unwrap();
+
+    let explorer = Explorer::detect_version()
+        .include(vec!["test_cgroup_explorer_*".to_string()])
+        .exclude(vec![dropped_name.to_string()])
+        .build()?;
+    assert!(
+        explorer.iter_cgroups().any(|c| c.path().ends_with(kept_name)),
+        "kept cgroup should be matched by the include glob"
+    );
+    assert!(
+        !explorer
+            .iter_cgroups()
+            .any(|c| c.path().ends_with(dropped_name)),
+        "dropped cgroup should be removed by the exclude glob"
+    );
+
+    kept.delete()?;
+    dropped.delete()?;
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn explore_path_and_rootfilesin_prefixes() -> anyhow::Result<()> {
+    let kept_name = "test_cgroup_explorer_prefix_kept";
+    let other_name = "test_cgroup_explorer_prefix_other";
+    for name in [kept_name, other_name] {
+        let existing_cgroup = Cgroup::load(auto(), auto().root().join(name));
+        let _ = existing_cgroup.delete();
+    }
+    let kept: Cgroup = CgroupBuilder::new(kept_name)
+        .memory()
+        .done()
+        .build(auto())
+        .unwrap();
+    let other: Cgroup = CgroupBuilder::new(other_name)
+        .memory()
+        .done()
+        .build(auto())
+        .unwrap();
+
+    let path_only = Explorer::detect_version()
+        .include(vec![format!("path:{kept_name}")])
+        .build()?;
+    assert!(
+        path_only.iter_cgroups().any(|c| c.path().ends_with(kept_name)),
+        "path: prefix should match its exact literal relative path"
+    );
+    assert!(
+        !path_only
+            .iter_cgroups()
+            .any(|c| c.path().ends_with(other_name)),
+        "path: prefix should not match any other path"
+    );
+
+    let root_children = Explorer::detect_version()
+        .include(vec!["rootfilesin:".to_string()])
+        .build()?;
+    assert!(
+        root_children
+            .iter_cgroups()
+            .any(|c| c.path().ends_with(kept_name))
+            && root_children
+                .iter_cgroups()
+                .any(|c| c.path().ends_with(other_name)),
+        "rootfilesin: prefix should match every direct child of the given directory"
+    );
+
+    kept.delete()?;
+    other.delete()?;
+
+    Ok(())
+}
+
 #[test]
 #[serial]
 #[cfg(feature = "regex")]